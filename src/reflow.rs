@@ -1,5 +1,10 @@
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::iter::Peekable;
+use std::str::Lines;
+
 use textwrap::wrap;
-use unicode_width::UnicodeWidthStr;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 /// Determines if a line is a natural paragraph break.
 ///
@@ -44,9 +49,110 @@ fn calculate_average_line_length(text: &str) -> f32 {
     total_length as f32 / lines.len() as f32
 }
 
+/// The line-wrapping algorithm used once a paragraph exceeds `para_chars_limit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WrapAlgorithm {
+    /// Greedy wrapping via `textwrap::wrap`: fills each line as full as possible,
+    /// which can leave a very ragged right edge.
+    Greedy,
+    /// Dynamic-programming optimal-fit wrapping that minimizes raggedness across
+    /// the whole paragraph, similar to the line-breaking algorithm used by TeX.
+    OptimalFit,
+}
+
+/// How an individual word wider than `para_chars_limit` gets broken across
+/// lines. Only invoked for such over-long words (URLs, long compound words,
+/// CJK runs with no spaces) — ordinary wrapping of words that already fit is
+/// untouched.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum WordSplitter {
+    /// Defer entirely to `textwrap`'s own word splitting and forced line
+    /// breaking. This is the default, so existing callers see no change.
+    /// Under `WrapAlgorithm::OptimalFit`, which does not call `textwrap`, an
+    /// over-long word is left whole and its line exceeds `para_chars_limit`.
+    #[default]
+    Default,
+    /// Never split a long word; the line containing it is left to exceed
+    /// `para_chars_limit`.
+    NoSplit,
+    /// Insert hard breaks at character boundaries, measuring with
+    /// `UnicodeWidthStr`/`UnicodeWidthChar` so a wide glyph (e.g. CJK) never
+    /// straddles the limit. This holds only when `para_chars_limit` can admit
+    /// the widest glyph in the word; a limit narrower than some glyph's own
+    /// width still emits that glyph as its own over-limit piece, since a
+    /// single character cannot be split further.
+    HardBreak,
+    /// Break only at caller-supplied byte offsets into the word — typically
+    /// sourced from a language hyphenation dictionary keyed by the word
+    /// itself — inserting a `-` at the latest offset that keeps the line
+    /// within `para_chars_limit`. Words absent from the dictionary fall back
+    /// to `HardBreak`.
+    Hyphenate(HashMap<String, Vec<usize>>),
+}
+
+/// Controls how `reflow_text`/`reflow_lines` decide where one paragraph ends and
+/// the next begins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReflowMode {
+    /// Guess paragraph boundaries heuristically from line length and trailing
+    /// punctuation, via `is_natural_paragraph_break` (the original behavior).
+    Heuristic,
+    /// Recognize RFC 3676 "format=flowed" text: a line ending in a trailing space
+    /// is a soft break to be rejoined with the next line, and a line without one
+    /// is a hard paragraph break. `>`-quoted lines are grouped by quote depth so
+    /// quoted and unquoted text reflow independently.
+    FormatFlowed,
+}
+
+/// Which line-ending convention `reflow_text` re-emits between output lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    /// Always join lines with a bare `\n`.
+    Lf,
+    /// Always join lines with `\r\n`.
+    CrLf,
+    /// Detect the dominant ending in the input (counting `\r\n` occurrences
+    /// against lone `\n` occurrences) and use that for every output line.
+    Auto,
+}
+
 pub struct ReflowOptions {
     pub threshold_ratio: f32,
     pub para_chars_limit: usize,
+    /// Which algorithm to use when a paragraph needs to be wrapped.
+    pub wrap_algorithm: WrapAlgorithm,
+    /// Fixed cost charged per line break when `wrap_algorithm` is `OptimalFit`.
+    /// Raise this to prefer fewer, fuller lines; lower it to tolerate more line
+    /// breaks in exchange for evenness.
+    pub nline_penalty: f64,
+    /// Multiplier applied to the squared overflow (in display-width units) when a
+    /// candidate line exceeds `para_chars_limit` under `OptimalFit`. This should be
+    /// large relative to `nline_penalty` so the algorithm only overflows a line
+    /// when there is no other option (e.g. a single word longer than the limit).
+    pub overflow_penalty: f64,
+    /// How paragraph boundaries are detected. Defaults to `Heuristic`.
+    pub mode: ReflowMode,
+    /// When `true`, re-emit the reflowed text as RFC 3676 format=flowed: every
+    /// wrapped continuation line (all but the last line of a paragraph) gets a
+    /// trailing space appended so the result round-trips through flowed-aware
+    /// clients.
+    pub emit_format_flowed: bool,
+    /// When `true`, detect each line's leading structural prefix (indentation,
+    /// `>` quote markers, list bullets) and treat a change in prefix as a hard
+    /// paragraph boundary, re-applying the prefix as a hanging indent once the
+    /// paragraph is wrapped. This keeps indented blocks, lists, and block quotes
+    /// from being flattened into one run-on line. Takes precedence over `mode`.
+    pub preserve_prefixes: bool,
+    /// Which line-ending convention `reflow_text` uses to join its output
+    /// lines. Defaults to `Lf`, matching the crate's original behavior; set
+    /// this to `Auto` to round-trip a CRLF document without rewriting its
+    /// line-ending convention. Has no effect on `reflow_lines`, whose items
+    /// are already terminator-free and left for the caller to join.
+    pub line_ending: LineEnding,
+    /// How to break an individual word that is itself wider than
+    /// `para_chars_limit`. Defaults to `WordSplitter::Default`, matching the
+    /// crate's original behavior.
+    pub word_splitter: WordSplitter,
 }
 
 impl Default for ReflowOptions {
@@ -54,79 +160,614 @@ impl Default for ReflowOptions {
         ReflowOptions {
             threshold_ratio: 0.9,
             para_chars_limit: usize::MAX,
+            wrap_algorithm: WrapAlgorithm::Greedy,
+            nline_penalty: 1.0,
+            overflow_penalty: 1000.0,
+            mode: ReflowMode::Heuristic,
+            emit_format_flowed: false,
+            preserve_prefixes: false,
+            line_ending: LineEnding::Lf,
+            word_splitter: WordSplitter::Default,
         }
     }
 }
 
-/// Reflow the given text to minimize artificial line breaks and break paragraphs based on word limits.
-///
-/// # Arguments
-/// * `text` - The input text containing lines that may have artificial line breaks.
-/// * `options` - The reflow options containing threshold_ratio and word_limit.
+/// Resolve the line-ending configured in `options` against the dominant
+/// ending actually present in `text`. The endings are counted on the raw
+/// text, before `str::lines` splits it and drops the `\r` of each `\r\n`.
+fn resolve_line_ending(text: &str, line_ending: LineEnding) -> &'static str {
+    match line_ending {
+        LineEnding::Lf => "\n",
+        LineEnding::CrLf => "\r\n",
+        LineEnding::Auto => {
+            let crlf_count = text.matches("\r\n").count();
+            let lf_only_count = text.matches('\n').count() - crlf_count;
+            if crlf_count > lf_only_count { "\r\n" } else { "\n" }
+        }
+    }
+}
+
+/// Split an RFC 3676 quote prefix off the front of `line`, returning the quote
+/// depth (the number of leading `>` characters) and the remaining content with
+/// at most one quote-stuffed space after the markers removed.
+fn split_quote_prefix(line: &str) -> (usize, &str) {
+    let mut depth = 0;
+    let mut rest = line;
+    while let Some(stripped) = rest.strip_prefix('>') {
+        depth += 1;
+        rest = stripped;
+    }
+    let content = if depth > 0 { rest.strip_prefix(' ').unwrap_or(rest) } else { rest };
+    (depth, content)
+}
+
+/// Split a structural leading prefix off the front of `line` for
+/// `preserve_prefixes`: a run of leading whitespace, followed by `>` quote
+/// markers, followed by at most one list bullet (`-`, `*`, `+`, or a numbered
+/// marker like `1.`/`2)`). Returns `(prefix, rest, has_bullet)`, where
+/// `has_bullet` tells a caller this line itself opens a list item, as opposed
+/// to merely continuing one under a matching hanging indent.
+fn detect_structural_prefix(line: &str) -> (&str, &str, bool) {
+    let mut idx = 0;
+    let bytes = line.as_bytes();
+
+    while idx < bytes.len() && (bytes[idx] == b' ' || bytes[idx] == b'\t') {
+        idx += 1;
+    }
+
+    while idx < bytes.len() && bytes[idx] == b'>' {
+        idx += 1;
+        if idx < bytes.len() && bytes[idx] == b' ' {
+            idx += 1;
+        }
+    }
+
+    let bullet_len = detect_bullet_len(&line[idx..]);
+    idx += bullet_len;
+
+    (&line[..idx], &line[idx..], bullet_len > 0)
+}
+
+/// Whether a structural prefix is quote-flavored, i.e. contains a `>` marker
+/// rather than being a plain indent or list bullet. Quote markers are restated
+/// on every line of a quoted paragraph, unlike list bullets, so a repeated
+/// quote prefix still counts as a paragraph continuation.
+fn prefix_is_quote(prefix: &str) -> bool {
+    prefix.trim_start_matches([' ', '\t']).starts_with('>')
+}
+
+/// Length in bytes of a single leading list bullet in `s` (0 if there is none).
+/// Recognizes `-`, `*`, `+`, and numbered markers like `1.` or `2)`, each of
+/// which must be followed by a space to count as a bullet.
+fn detect_bullet_len(s: &str) -> usize {
+    let mut chars = s.char_indices();
+
+    if let Some((_, c @ ('-' | '*' | '+'))) = chars.next() {
+        let after = c.len_utf8();
+        return if s[after..].starts_with(' ') { after + 1 } else { 0 };
+    }
+
+    let digits_end = s.char_indices().take_while(|(_, c)| c.is_ascii_digit()).last();
+    let Some((last_idx, last_char)) = digits_end else {
+        return 0;
+    };
+    let after_digits = last_idx + last_char.len_utf8();
+
+    match s[after_digits..].chars().next() {
+        Some(sep @ ('.' | ')')) => {
+            let after_sep = after_digits + sep.len_utf8();
+            if s[after_sep..].starts_with(' ') {
+                after_sep + 1
+            } else {
+                0
+            }
+        }
+        _ => 0,
+    }
+}
+
+/// Wrap `paragraph` into lines no wider than `target_width` display columns using
+/// a dynamic program that minimizes the total raggedness of the paragraph, rather
+/// than greedily filling each line.
 ///
-/// # Returns
-/// * A `String` containing the reflowed text with artificial line breaks minimized and paragraphs split according to word limit.
-pub fn reflow_text(text: &str, options: Option<ReflowOptions>) -> String {
-    // If the user provides options, use them; otherwise, use default values.
-    let options = options.unwrap_or(ReflowOptions::default());
+/// Let `mincost[j]` be the minimum total cost of breaking the first `j` words into
+/// lines. For a candidate line spanning words `i..j`, the cost is the squared gap
+/// between the line width and `target_width` (waived for the final line, which is
+/// allowed to be short), plus a fixed `nline_penalty` per line, plus a heavily
+/// weighted squared-overflow penalty if the line is wider than `target_width`.
+/// Breaks are reconstructed from a backpointer array once `mincost[n]` is known.
+fn optimal_fit_wrap(paragraph: &str, target_width: usize, nline_penalty: f64, overflow_penalty: f64) -> Vec<String> {
+    let words: Vec<&str> = paragraph.split_whitespace().collect();
+    if words.is_empty() {
+        return Vec::new();
+    }
 
-    // Split the input text into lines.
-    let lines: Vec<&str> = text.lines().collect();
+    let n = words.len();
+    let target_width = target_width as f64;
+
+    // width[k] = cumulative display width of words[0..k] (no separating spaces).
+    let mut width = vec![0f64; n + 1];
+    for k in 0..n {
+        width[k + 1] = width[k] + UnicodeWidthStr::width(words[k]) as f64;
+    }
+
+    let line_width = |i: usize, j: usize| -> f64 {
+        // j - i words joined by single spaces.
+        width[j] - width[i] + (j - i - 1) as f64
+    };
 
-    // Calculate the average line length.
-    let avg_line_length = lines.iter().map(|line| line.len()).sum::<usize>().checked_div(lines.len()).unwrap_or(0);
+    let mut mincost = vec![f64::INFINITY; n + 1];
+    let mut backptr = vec![0usize; n + 1];
+    mincost[0] = 0.0;
 
-    // Initialize a buffer to store the modified lines.
-    let mut buffer = String::new();
+    for j in 1..=n {
+        for i in 0..j {
+            let w = line_width(i, j);
+            let is_last_line = j == n;
 
-    // Iterate through the input lines.
-    for (index, line) in lines.iter().enumerate() {
-        // Check if the current line is a natural paragraph break (shorter than the threshold).
-        if is_natural_paragraph_break(line, avg_line_length as f32, options.threshold_ratio) {
-            // If the buffer is not empty, add it to the result with a newline.
-            if !buffer.is_empty() {
-                buffer.push_str(line);
-                buffer.push('\n');
+            let cost = if w <= target_width {
+                if is_last_line {
+                    0.0
+                } else {
+                    let gap = target_width - w;
+                    gap * gap
+                }
             } else {
-                // If the buffer is empty, just add the current line to the result with a newline.
-                buffer.push_str(line);
-                buffer.push('\n');
+                let overflow = w - target_width;
+                overflow * overflow * overflow_penalty
+            } + nline_penalty;
+
+            let total = mincost[i] + cost;
+            if total < mincost[j] {
+                mincost[j] = total;
+                backptr[j] = i;
+            }
+        }
+    }
+
+    // Reconstruct the line breaks by walking the backpointers from the end.
+    let mut breaks = Vec::new();
+    let mut j = n;
+    while j > 0 {
+        let i = backptr[j];
+        breaks.push((i, j));
+        j = i;
+    }
+    breaks.reverse();
+
+    breaks.into_iter().map(|(i, j)| words[i..j].join(" ")).collect()
+}
+
+/// Break a single over-long `word` into pieces at character boundaries, never
+/// letting a piece's display width (per `UnicodeWidthChar`) exceed `limit`.
+/// Since each piece is built one whole character at a time, a wide glyph is
+/// never split across pieces — so this guarantee only holds when `limit` can
+/// admit the widest glyph in `word`. If `limit` is smaller than some glyph's
+/// own width (e.g. `limit: 1` against a double-width CJK character), that
+/// glyph still becomes its own piece and overflows `limit`, since a single
+/// character cannot be split further.
+fn hard_break_word(word: &str, limit: usize) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut current = String::new();
+    let mut current_width = 0usize;
+
+    for ch in word.chars() {
+        let ch_width = UnicodeWidthChar::width(ch).unwrap_or(0);
+        if current_width + ch_width > limit && !current.is_empty() {
+            pieces.push(std::mem::take(&mut current));
+            current_width = 0;
+        }
+        current.push(ch);
+        current_width += ch_width;
+    }
+    if !current.is_empty() {
+        pieces.push(current);
+    }
+
+    pieces
+}
+
+/// Break `word` using `dictionary`'s caller-supplied hyphenation points,
+/// inserting a `-` at the latest point that keeps each piece within `limit`
+/// display columns. Falls back to `hard_break_word` wherever the dictionary
+/// has no entry for `word`, or once its break points run out but the
+/// remainder is still too wide.
+fn hyphenate_word(word: &str, limit: usize, dictionary: &HashMap<String, Vec<usize>>) -> Vec<String> {
+    let Some(break_points) = dictionary.get(word) else {
+        return hard_break_word(word, limit);
+    };
+    let mut break_points: Vec<usize> = break_points.clone();
+    break_points.sort_unstable();
+    break_points.dedup();
+
+    let mut pieces = Vec::new();
+    let mut start = 0usize;
+
+    loop {
+        let mut chosen = None;
+        for &point in break_points.iter().filter(|&&point| point > start) {
+            // A dictionary-supplied offset isn't guaranteed to land on a char
+            // boundary; slicing there would panic, so just skip it.
+            if !word.is_char_boundary(point) {
+                continue;
+            }
+            // Width grows monotonically with `point`, so the first piece
+            // that overflows means every later one will too.
+            if UnicodeWidthStr::width(&word[start..point]) + 1 > limit {
+                break;
             }
-        } else {
-            // If the line is not a natural paragraph break, add it to the buffer.
-            buffer.push_str(line);
+            chosen = Some(point);
+        }
 
-            // If it's not the last line, add a space to the buffer.
-            if index != lines.len() - 1 {
-                buffer.push(' ');
+        match chosen {
+            Some(point) => {
+                pieces.push(format!("{}-", &word[start..point]));
+                start = point;
             }
+            None => break,
         }
     }
 
-    // break the buffers into lines and wrap paragraphs that are longer than the word limit.
-    let mut result = String::new();
-    for paragraph in buffer.lines() {
-        let char_count = paragraph.chars().count();
+    // `start` is always 0 or a previously chosen `point`, both checked above.
+    let remainder = &word[start..];
+    if UnicodeWidthStr::width(remainder) <= limit {
+        pieces.push(remainder.to_string());
+    } else {
+        pieces.extend(hard_break_word(remainder, limit));
+    }
 
-        // if the number of chars is less than the word limit, just add the paragraph to the result
-        if char_count <= options.para_chars_limit {
-            result.push_str(paragraph);
-            result.push('\n');
-            continue;
-        } else {
+    pieces
+}
 
-            // otherwise, wrap the paragraph and add it to the result
-            let wrapped_paragraph = wrap(paragraph, options.para_chars_limit);
-            for line in wrapped_paragraph {
-                result.push_str(&line.to_string());
-                result.push('\n');
+/// Pre-split every word in `paragraph` that is individually wider than
+/// `limit`, joining the pieces a `word_splitter` produces with spaces so the
+/// wrapping algorithm that runs afterward treats them as ordinary words.
+/// Words that already fit are passed through untouched.
+fn split_long_words(paragraph: &str, limit: usize, word_splitter: &WordSplitter) -> String {
+    paragraph
+        .split(' ')
+        .map(|word| {
+            if UnicodeWidthStr::width(word) <= limit {
+                return word.to_string();
+            }
+            match word_splitter {
+                WordSplitter::Default | WordSplitter::NoSplit => word.to_string(),
+                WordSplitter::HardBreak => hard_break_word(word, limit).join(" "),
+                WordSplitter::Hyphenate(dictionary) => hyphenate_word(word, limit, dictionary).join(" "),
             }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Wrap a finished paragraph according to `options`, pushing the resulting line(s)
+/// onto `pending`. Shared by every `ReflowIter` mode, and so by `reflow_text`,
+/// which collects a `ReflowIter`.
+fn wrap_paragraph_into(paragraph: &str, limit: usize, options: &ReflowOptions, pending: &mut VecDeque<String>) {
+    let char_count = paragraph.chars().count();
+
+    // If the paragraph already fits, add it as-is. For `WordSplitter::Default`
+    // this mirrors the crate's original char-count check; any other splitter
+    // promises to keep display width within `limit`, so it must be judged by
+    // `UnicodeWidthStr::width` instead — a char count can understate the
+    // width of a run of wide (e.g. CJK) characters and let it slip past
+    // `HardBreak`/`Hyphenate` untouched.
+    let fits = match options.word_splitter {
+        WordSplitter::Default => char_count <= limit,
+        _ => UnicodeWidthStr::width(paragraph) <= limit,
+    };
+    if fits {
+        pending.push_back(paragraph.to_string());
+        return;
+    }
+
+    let prepared;
+    let paragraph = match options.word_splitter {
+        WordSplitter::Default => paragraph,
+        _ => {
+            prepared = split_long_words(paragraph, limit, &options.word_splitter);
+            prepared.as_str()
         }
     };
 
-    result.pop();
+    let start_len = pending.len();
+
+    match options.wrap_algorithm {
+        WrapAlgorithm::Greedy => {
+            let lines = match options.word_splitter {
+                // The original default: let `textwrap` find and force-break
+                // over-long words itself.
+                WordSplitter::Default => wrap(paragraph, limit),
+                // Words wider than `limit` have already been pre-split (or
+                // deliberately left alone for `NoSplit`), so tell `textwrap`
+                // not to do any splitting of its own.
+                _ => wrap(
+                    paragraph,
+                    textwrap::Options::new(limit)
+                        .word_separator(textwrap::WordSeparator::AsciiSpace)
+                        .word_splitter(textwrap::WordSplitter::NoHyphenation)
+                        .break_words(false),
+                ),
+            };
+            for line in lines {
+                pending.push_back(line.to_string());
+            }
+        }
+        WrapAlgorithm::OptimalFit => {
+            for line in optimal_fit_wrap(paragraph, limit, options.nline_penalty, options.overflow_penalty) {
+                pending.push_back(line);
+            }
+        }
+    }
+
+    // RFC 3676 format=flowed: every continuation line (all but the last line
+    // of this paragraph) gets a trailing space, regardless of which mode or
+    // wrap algorithm produced it.
+    if options.emit_format_flowed {
+        let added = pending.len() - start_len;
+        if added > 1 {
+            for (i, line) in pending.iter_mut().skip(start_len).enumerate() {
+                if i != added - 1 {
+                    line.push(' ');
+                }
+            }
+        }
+    }
+}
 
-    result
+/// A lazy, paragraph-at-a-time reflow of the source text.
+///
+/// Unlike `reflow_text`, this never materializes the whole document: it pulls
+/// just enough lines from the source to finish the next paragraph (or wrapped
+/// line within an over-long paragraph), wraps it, and hands it back. Peak memory
+/// is proportional to one paragraph rather than the whole input, which matters
+/// for callers like pagers or viewers that only need the first screenful.
+pub struct ReflowIter<'a> {
+    lines: Peekable<Lines<'a>>,
+    avg_line_length: f32,
+    options: ReflowOptions,
+    /// Lines of the paragraph currently being assembled from the source, joined
+    /// with single spaces, mirroring the `buffer` built by the old two-pass code.
+    join_buffer: String,
+    /// Already-wrapped lines ready to be handed out by `next()`.
+    pending: VecDeque<String>,
+    source_exhausted: bool,
+    /// `ReflowMode::FormatFlowed` only: the quote depth of the paragraph currently
+    /// being assembled in `join_buffer`.
+    flowed_quote_depth: usize,
+    /// `preserve_prefixes` only: the structural prefix of the paragraph currently
+    /// being assembled in `join_buffer`.
+    structural_prefix: String,
+}
+
+impl<'a> ReflowIter<'a> {
+    fn new(text: &'a str, options: ReflowOptions) -> Self {
+        // The average line length is a property of the whole document, so it is
+        // still computed up front, but as a streaming sum/count rather than a
+        // materialized `Vec` — peak memory stays proportional to one paragraph.
+        let (total_len, line_count) = text.lines().fold((0usize, 0usize), |(total, count), line| (total + line.len(), count + 1));
+        let avg_line_length = total_len.checked_div(line_count).unwrap_or(0) as f32;
+
+        ReflowIter {
+            lines: text.lines().peekable(),
+            avg_line_length,
+            options,
+            join_buffer: String::new(),
+            pending: VecDeque::new(),
+            source_exhausted: false,
+            flowed_quote_depth: 0,
+            structural_prefix: String::new(),
+        }
+    }
+
+    /// Pull source lines and grow `join_buffer` until a paragraph is complete,
+    /// then wrap it into `pending`. Stops as soon as `pending` has something to
+    /// give `next()`, or the source runs out.
+    fn advance(&mut self) {
+        if self.options.preserve_prefixes {
+            self.advance_preserve_prefixes();
+            return;
+        }
+        match self.options.mode {
+            ReflowMode::Heuristic => self.advance_heuristic(),
+            ReflowMode::FormatFlowed => self.advance_format_flowed(),
+        }
+    }
+
+    fn advance_preserve_prefixes(&mut self) {
+        while self.pending.is_empty() {
+            match self.lines.next() {
+                Some(line) => {
+                    // A blank line is always a hard paragraph boundary, and is kept
+                    // as its own blank output line rather than merged into either
+                    // neighbor's prefix.
+                    if line.is_empty() {
+                        if !self.join_buffer.is_empty() {
+                            let paragraph = self.join_buffer.trim_end().to_string();
+                            self.join_buffer.clear();
+                            let prefix = std::mem::take(&mut self.structural_prefix);
+                            self.wrap_prefixed_paragraph(&paragraph, &prefix);
+                        }
+                        self.pending.push_back(String::new());
+                        continue;
+                    }
+
+                    let (prefix, content, has_bullet) = detect_structural_prefix(line);
+
+                    // A line continues the paragraph if its prefix is blank space of
+                    // the same display width as the established one (a list or indent
+                    // continuation line under its marker), or, for `>` quote paragraphs
+                    // only, repeats the established prefix verbatim (every quoted line
+                    // restates its markers). A line that opens its own bullet is always
+                    // a new item, even inside a quote, so it never continues. Any other
+                    // prefix is a hard paragraph boundary.
+                    let hanging_indent = " ".repeat(UnicodeWidthStr::width(self.structural_prefix.as_str()));
+                    let continues_paragraph = !has_bullet
+                        && (prefix == hanging_indent || (prefix_is_quote(&self.structural_prefix) && prefix == self.structural_prefix));
+
+                    if !self.join_buffer.is_empty() && !continues_paragraph {
+                        // The line that just ended the paragraph was itself a
+                        // continuation, so `join_buffer` has a trailing separator
+                        // space queued up for a next line that never came.
+                        let paragraph = self.join_buffer.trim_end().to_string();
+                        self.join_buffer.clear();
+                        let finished_prefix = std::mem::replace(&mut self.structural_prefix, prefix.to_string());
+                        self.wrap_prefixed_paragraph(&paragraph, &finished_prefix);
+                    } else if self.join_buffer.is_empty() {
+                        self.structural_prefix = prefix.to_string();
+                    }
+
+                    self.join_buffer.push_str(content);
+                    if self.lines.peek().is_some() {
+                        self.join_buffer.push(' ');
+                    }
+                }
+                None => {
+                    if !self.join_buffer.is_empty() {
+                        let paragraph = std::mem::take(&mut self.join_buffer);
+                        let prefix = std::mem::take(&mut self.structural_prefix);
+                        self.wrap_prefixed_paragraph(&paragraph, &prefix);
+                    }
+                    self.source_exhausted = true;
+                    break;
+                }
+            }
+        }
+    }
+
+    fn advance_heuristic(&mut self) {
+        while self.pending.is_empty() {
+            match self.lines.next() {
+                Some(line) => {
+                    if is_natural_paragraph_break(line, self.avg_line_length, self.options.threshold_ratio) {
+                        self.join_buffer.push_str(line);
+                        let paragraph = std::mem::take(&mut self.join_buffer);
+                        wrap_paragraph_into(&paragraph, self.options.para_chars_limit, &self.options, &mut self.pending);
+                    } else {
+                        self.join_buffer.push_str(line);
+                        if self.lines.peek().is_some() {
+                            self.join_buffer.push(' ');
+                        }
+                    }
+                }
+                None => {
+                    if !self.join_buffer.is_empty() {
+                        let paragraph = std::mem::take(&mut self.join_buffer);
+                        wrap_paragraph_into(&paragraph, self.options.para_chars_limit, &self.options, &mut self.pending);
+                    }
+                    self.source_exhausted = true;
+                    break;
+                }
+            }
+        }
+    }
+
+    fn advance_format_flowed(&mut self) {
+        while self.pending.is_empty() {
+            match self.lines.next() {
+                Some(line) => {
+                    let (depth, content) = split_quote_prefix(line);
+                    let soft_break = content.ends_with(' ');
+
+                    // A change in quote depth is always a hard paragraph boundary,
+                    // even if the previous line ended in a soft-break space.
+                    if !self.join_buffer.is_empty() && depth != self.flowed_quote_depth {
+                        let paragraph = std::mem::take(&mut self.join_buffer);
+                        self.wrap_flowed_paragraph(&paragraph, self.flowed_quote_depth);
+                    }
+
+                    self.flowed_quote_depth = depth;
+
+                    if soft_break {
+                        // Strip exactly the one trailing space that marks the soft
+                        // break; it still serves as the word separator once rejoined.
+                        self.join_buffer.push_str(&content[..content.len() - 1]);
+                        self.join_buffer.push(' ');
+                    } else {
+                        self.join_buffer.push_str(content);
+                        let paragraph = std::mem::take(&mut self.join_buffer);
+                        self.wrap_flowed_paragraph(&paragraph, depth);
+                    }
+                }
+                None => {
+                    if !self.join_buffer.is_empty() {
+                        let paragraph = std::mem::take(&mut self.join_buffer);
+                        self.wrap_flowed_paragraph(&paragraph, self.flowed_quote_depth);
+                    }
+                    self.source_exhausted = true;
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Wrap a flowed paragraph and re-apply its quote prefix to every
+    /// resulting line. The format=flowed continuation-line trailing space (if
+    /// any) is already applied by `wrap_paragraph_into`.
+    fn wrap_flowed_paragraph(&mut self, paragraph: &str, depth: usize) {
+        let prefix = if depth > 0 { format!("{} ", ">".repeat(depth)) } else { String::new() };
+
+        let mut wrapped = VecDeque::new();
+        wrap_paragraph_into(paragraph, self.options.para_chars_limit, &self.options, &mut wrapped);
+
+        for line in wrapped {
+            self.pending.push_back(format!("{prefix}{line}"));
+        }
+    }
+
+    /// Wrap a prefixed paragraph and re-apply its structural prefix to the
+    /// first output line, padding every continuation line with a hanging
+    /// indent of equal display width computed via `UnicodeWidthStr`.
+    fn wrap_prefixed_paragraph(&mut self, paragraph: &str, prefix: &str) {
+        let prefix_width = UnicodeWidthStr::width(prefix);
+        let hanging_indent = " ".repeat(prefix_width);
+        let limit = self.options.para_chars_limit.saturating_sub(prefix_width).max(1);
+
+        let mut wrapped = VecDeque::new();
+        wrap_paragraph_into(paragraph, limit, &self.options, &mut wrapped);
+
+        for (i, line) in wrapped.into_iter().enumerate() {
+            let lead = if i == 0 { prefix } else { hanging_indent.as_str() };
+            self.pending.push_back(format!("{lead}{line}"));
+        }
+    }
+}
+
+impl<'a> Iterator for ReflowIter<'a> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        if self.pending.is_empty() && !self.source_exhausted {
+            self.advance();
+        }
+        self.pending.pop_front()
+    }
+}
+
+/// Reflow `text` lazily, one finished (and already-wrapped) line at a time.
+///
+/// # Arguments
+/// * `text` - The input text containing lines that may have artificial line breaks.
+/// * `options` - The reflow options containing threshold_ratio and para_chars_limit.
+///
+/// # Returns
+/// * A `ReflowIter` yielding reflowed lines without buffering the whole output.
+pub fn reflow_lines(text: &str, options: Option<ReflowOptions>) -> ReflowIter<'_> {
+    ReflowIter::new(text, options.unwrap_or_default())
+}
+
+/// Reflow the given text to minimize artificial line breaks and break paragraphs based on word limits.
+///
+/// # Arguments
+/// * `text` - The input text containing lines that may have artificial line breaks.
+/// * `options` - The reflow options containing threshold_ratio and para_chars_limit.
+///
+/// # Returns
+/// * A `String` containing the reflowed text with artificial line breaks minimized and paragraphs split according to word limit.
+pub fn reflow_text(text: &str, options: Option<ReflowOptions>) -> String {
+    let options = options.unwrap_or_default();
+    let line_ending = resolve_line_ending(text, options.line_ending);
+    reflow_lines(text, Some(options)).collect::<Vec<_>>().join(line_ending)
 }
 
 #[cfg(test)]
@@ -152,9 +793,289 @@ mod tests {
         let options = ReflowOptions {
             threshold_ratio: 0.9,
             para_chars_limit: 10,
+            ..ReflowOptions::default()
         };
         let output = reflow_text(input, Some(options));
         let output_lines: Vec<&str> = output.lines().collect();
         assert_eq!(output_lines, expected_output_with_options)
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_line_ending_auto_detects_crlf() {
+        let input = "first paragraph\r\n\r\nsecond paragraph";
+
+        let options = ReflowOptions {
+            preserve_prefixes: true,
+            line_ending: LineEnding::Auto,
+            ..ReflowOptions::default()
+        };
+        let output = reflow_text(input, Some(options));
+
+        assert_eq!(output, "first paragraph\r\n\r\nsecond paragraph");
+    }
+
+    #[test]
+    fn test_line_ending_explicit_crlf_overrides_lf_input() {
+        let input = "first paragraph\n\nsecond paragraph";
+
+        let options = ReflowOptions {
+            preserve_prefixes: true,
+            line_ending: LineEnding::CrLf,
+            ..ReflowOptions::default()
+        };
+        let output = reflow_text(input, Some(options));
+
+        assert_eq!(output, "first paragraph\r\n\r\nsecond paragraph");
+    }
+
+    #[test]
+    fn test_reflow_text_optimal_fit() {
+        let input = "This is a test of the optimal fit wrap algorithm which should \
+        produce lines that are more even than the greedy wrapper.";
+
+        let options = ReflowOptions {
+            threshold_ratio: 0.9,
+            para_chars_limit: 20,
+            wrap_algorithm: WrapAlgorithm::OptimalFit,
+            ..ReflowOptions::default()
+        };
+        let output = reflow_text(input, Some(options));
+
+        // Every produced line must respect the limit, and no word may be dropped.
+        let mut words = Vec::new();
+        for line in output.lines() {
+            assert!(UnicodeWidthStr::width(line) <= 20);
+            words.extend(line.split_whitespace());
+        }
+        assert_eq!(words.join(" "), input.split_whitespace().collect::<Vec<_>>().join(" "));
+    }
+
+    #[test]
+    fn test_word_splitter_no_split_allows_overflow() {
+        let input = "short https://example.com/a-very-long-url-that-does-not-fit-on-one-line word";
+
+        let options = ReflowOptions {
+            para_chars_limit: 15,
+            word_splitter: WordSplitter::NoSplit,
+            ..ReflowOptions::default()
+        };
+        let output = reflow_text(input, Some(options));
+
+        assert!(output.lines().any(|line| line == "https://example.com/a-very-long-url-that-does-not-fit-on-one-line"));
+    }
+
+    #[test]
+    fn test_word_splitter_hard_break_respects_limit() {
+        let input = "short https://example.com/a-very-long-url-that-does-not-fit-on-one-line word";
+
+        let options = ReflowOptions {
+            para_chars_limit: 15,
+            word_splitter: WordSplitter::HardBreak,
+            ..ReflowOptions::default()
+        };
+        let output = reflow_text(input, Some(options));
+
+        for line in output.lines() {
+            assert!(UnicodeWidthStr::width(line) <= 15);
+        }
+        // Hard breaking never drops or reorders characters, only inserts breaks.
+        let rejoined: String = output.split_whitespace().collect();
+        let original: String = input.split_whitespace().collect();
+        assert_eq!(rejoined, original);
+    }
+
+    #[test]
+    fn test_word_splitter_hyphenate_uses_dictionary_break_points() {
+        let mut dictionary = HashMap::new();
+        dictionary.insert("hyphenation".to_string(), vec![2, 5, 8]);
+
+        let input = "try hyphenation here";
+
+        let options = ReflowOptions {
+            para_chars_limit: 6,
+            word_splitter: WordSplitter::Hyphenate(dictionary),
+            ..ReflowOptions::default()
+        };
+        let output = reflow_text(input, Some(options));
+
+        for line in output.lines() {
+            assert!(UnicodeWidthStr::width(line) <= 6);
+        }
+        assert!(output.lines().any(|line| line.ends_with('-')));
+    }
+
+    #[test]
+    fn test_word_splitter_hard_break_fires_on_wide_cjk_run_that_fits_char_count() {
+        // 10 chars wide enough to fit `para_chars_limit` under a char-count
+        // measure, but each is double-width, so the true display width (20)
+        // does not fit and `HardBreak` must still split it.
+        let input = "一二三四五六七八九十";
+
+        let options = ReflowOptions {
+            para_chars_limit: 12,
+            word_splitter: WordSplitter::HardBreak,
+            ..ReflowOptions::default()
+        };
+        let output = reflow_text(input, Some(options));
+
+        assert!(output.lines().count() > 1);
+        for line in output.lines() {
+            assert!(UnicodeWidthStr::width(line) <= 12);
+        }
+    }
+
+    #[test]
+    fn test_hyphenate_word_skips_break_point_inside_char_boundary() {
+        // Byte offset 1 lands inside the 3-byte UTF-8 encoding of '一', so it
+        // must be skipped rather than panicking on a non-boundary slice.
+        let mut dictionary = HashMap::new();
+        dictionary.insert("一二".to_string(), vec![1]);
+
+        let pieces = hyphenate_word("一二", 4, &dictionary);
+
+        assert_eq!(pieces, vec!["一二".to_string()]);
+    }
+
+    #[test]
+    fn test_hard_break_word_cannot_subdivide_a_glyph_wider_than_limit() {
+        // `limit: 1` is narrower than any of these double-width glyphs, so
+        // each one still becomes its own (over-limit) piece: a single
+        // character is the smallest unit `hard_break_word` can produce.
+        let pieces = hard_break_word("一二三", 1);
+
+        assert_eq!(pieces, vec!["一".to_string(), "二".to_string(), "三".to_string()]);
+        assert!(pieces.iter().any(|piece| UnicodeWidthStr::width(piece.as_str()) > 1));
+    }
+
+    #[test]
+    fn test_reflow_lines_matches_reflow_text() {
+        let input = "This is a test of the reflow text function. This text should be \
+        broken into multiple lines if the word limit is set to a small \
+        value. This line is intentionally short.";
+
+        let expected_text = reflow_text(input, None);
+        let expected: Vec<&str> = expected_text.lines().collect();
+        let streamed: Vec<String> = reflow_lines(input, None).collect();
+        assert_eq!(streamed, expected);
+
+        // .take(n) should only pull as many lines as requested.
+        let first_two: Vec<String> = reflow_lines(input, None).take(2).collect();
+        assert_eq!(first_two, expected[..2.min(expected.len())]);
+    }
+
+    #[test]
+    fn test_format_flowed_soft_and_hard_breaks() {
+        let input = "This is a soft \nbreak that should rejoin.\nThis is a new paragraph.\n> quoted soft \n> quoted hard.\nunquoted again.";
+
+        let options = ReflowOptions {
+            mode: ReflowMode::FormatFlowed,
+            ..ReflowOptions::default()
+        };
+        let output = reflow_text(input, Some(options));
+
+        assert_eq!(
+            output,
+            "This is a soft break that should rejoin.\nThis is a new paragraph.\n> quoted soft quoted hard.\nunquoted again."
+        );
+    }
+
+    #[test]
+    fn test_preserve_prefixes_keeps_list_and_quote_structure() {
+        let input = "- first item continues\n  here\n- second item\n> a quoted line\n> that continues\nplain paragraph\ncontinues here";
+
+        let options = ReflowOptions {
+            preserve_prefixes: true,
+            ..ReflowOptions::default()
+        };
+        let output = reflow_text(input, Some(options));
+
+        assert_eq!(
+            output,
+            "- first item continues here\n- second item\n> a quoted line that continues\nplain paragraph continues here"
+        );
+    }
+
+    #[test]
+    fn test_preserve_prefixes_bullet_inside_quote_starts_new_item() {
+        let input = "> - first item\n> - second item";
+
+        let options = ReflowOptions {
+            preserve_prefixes: true,
+            ..ReflowOptions::default()
+        };
+        let output = reflow_text(input, Some(options));
+
+        assert_eq!(output, "> - first item\n> - second item");
+    }
+
+    #[test]
+    fn test_preserve_prefixes_blank_line_is_a_hard_boundary() {
+        let input = "first paragraph\ncontinues\n\nsecond paragraph";
+
+        let options = ReflowOptions {
+            preserve_prefixes: true,
+            ..ReflowOptions::default()
+        };
+        let output = reflow_text(input, Some(options));
+
+        assert_eq!(output, "first paragraph continues\n\nsecond paragraph");
+    }
+
+    #[test]
+    fn test_preserve_prefixes_wraps_with_hanging_indent() {
+        let input = "- one two three four five six seven eight";
+
+        let options = ReflowOptions {
+            preserve_prefixes: true,
+            para_chars_limit: 12,
+            ..ReflowOptions::default()
+        };
+        let output = reflow_text(input, Some(options));
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert!(lines[0].starts_with("- "));
+        for line in &lines[1..] {
+            assert!(line.starts_with("  "));
+        }
+    }
+
+    #[test]
+    fn test_format_flowed_round_trip_emits_trailing_spaces() {
+        let input = "one two three four five six seven";
+
+        let options = ReflowOptions {
+            mode: ReflowMode::FormatFlowed,
+            para_chars_limit: 12,
+            emit_format_flowed: true,
+            ..ReflowOptions::default()
+        };
+        let output = reflow_text(input, Some(options));
+
+        for line in output.lines().take(output.lines().count() - 1) {
+            assert!(line.ends_with(' '));
+        }
+        assert!(!output.lines().last().unwrap().ends_with(' '));
+    }
+
+    #[test]
+    fn test_format_flowed_emits_trailing_spaces_outside_format_flowed_mode() {
+        // `emit_format_flowed` is meant to shape the output of any mode, not
+        // just `ReflowMode::FormatFlowed` — this is the default `Heuristic`
+        // mode, which shouldn't silently ignore the option.
+        let input = "one two three four five six seven eight";
+
+        let options = ReflowOptions {
+            para_chars_limit: 12,
+            emit_format_flowed: true,
+            ..ReflowOptions::default()
+        };
+        let output = reflow_text(input, Some(options));
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert!(lines.len() > 1);
+        for line in &lines[..lines.len() - 1] {
+            assert!(line.ends_with(' '));
+        }
+        assert!(!lines.last().unwrap().ends_with(' '));
+    }
+}